@@ -0,0 +1,56 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Deserialize;
+
+/// A single GPU's overclock settings within a profile, matched to a
+/// physical GPU by device ID so profiles survive reordering.
+#[derive(Debug, Deserialize)]
+pub struct ProfileEntry {
+    pub device_id: u32,
+    pub memclock: Option<i32>,
+    pub gpuclock: Option<i32>,
+    pub voltage: Option<i32>,
+    pub powerlimit: Option<u32>,
+    pub templimit: Option<i32>,
+}
+
+/// A named collection of per-GPU overclock settings, e.g. `quiet`, `mining`, `gaming`.
+#[derive(Debug, Deserialize)]
+pub struct Profile {
+    pub gpus: Vec<ProfileEntry>,
+}
+
+/// Top-level layout of a profile TOML file: profile name to profile.
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    #[serde(flatten)]
+    pub profiles: HashMap<String, Profile>,
+}
+
+impl Config {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Config, String> {
+        let contents = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read config file '{}': {}", path.as_ref().display(), e))?;
+
+        toml::from_str(&contents)
+            .map_err(|e| format!("Failed to parse config file '{}': {}", path.as_ref().display(), e))
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile, String> {
+        self.profiles.get(name)
+            .ok_or_else(|| format!("No profile named '{}' in config file", name))
+    }
+}
+
+/// Validate that every device ID referenced by a profile exists among the enumerated GPUs.
+pub fn validate_profile(profile: &Profile, device_ids: &[u32]) -> Result<(), String> {
+    for entry in &profile.gpus {
+        if !device_ids.contains(&entry.device_id) {
+            return Err(format!("Profile references unknown device ID {}", entry.device_id));
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,36 @@
+use thiserror::Error;
+
+/// Crate-wide error type. Each variant maps to a distinct process exit code
+/// so scripts driving this tool can branch on failure mode instead of just
+/// "it crashed".
+#[derive(Debug, Error)]
+pub enum AppError {
+    #[error("NVAPI error: {0}")]
+    Nvapi(#[from] nvapi::Status),
+
+    #[error("Invalid argument: {0}")]
+    Parse(String),
+
+    #[error("Configuration error: {0}")]
+    Config(String),
+
+    #[error("Validation error: {0}")]
+    Validation(String),
+}
+
+impl AppError {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            AppError::Nvapi(..) => 2,
+            AppError::Parse(..) => 3,
+            AppError::Config(..) => 4,
+            AppError::Validation(..) => 5,
+        }
+    }
+}
+
+impl From<String> for AppError {
+    fn from(message: String) -> Self {
+        AppError::Config(message)
+    }
+}
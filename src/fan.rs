@@ -0,0 +1,151 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use nvapi::Gpu;
+
+/// Discrete PID controller gains and duty bounds shared by every GPU in a
+/// `fan --target-temp` run.
+pub struct PidConfig {
+    pub target_temp: f64,
+    pub kp: f64,
+    pub ki: f64,
+    pub kd: f64,
+    pub base_duty: u32,
+    pub min_duty: u32,
+    pub max_duty: u32,
+}
+
+/// Per-GPU state carried across ticks: the previous error (for the
+/// derivative term) and the accumulated integral (clamped for anti-windup).
+struct PidState {
+    integral: f64,
+    prev_error: f64,
+    last_tick: Option<Instant>,
+}
+
+impl PidState {
+    fn new() -> Self {
+        PidState { integral: 0.0, prev_error: 0.0, last_tick: None }
+    }
+}
+
+/// Read one GPU's current temperature for this tick. A transient driver
+/// read failure, or a GPU reporting no thermal sensor, should not kill the
+/// whole control loop, so callers log and skip this GPU's tick instead of
+/// propagating the error.
+fn read_temperature(gpu: &Gpu) -> Result<f64, String> {
+    let thermal = gpu.inner().thermal_settings(None).map_err(|e| e.to_string())?;
+
+    Ok(thermal.get(0).ok_or("no thermal sensor reported")?.current_temperature as f64)
+}
+
+fn set_fan_duty(gpu: &Gpu, duty: u32) -> () {
+    match gpu.inner().set_cooler_levels([(0, duty)].iter().cloned()) {
+        Ok(..) => (),
+        Err(..) => eprintln!("GPU has no controllable cooler, skipping"),
+    }
+}
+
+/// Pure per-tick PID step: given the current integral/previous error and a
+/// fresh temperature reading, returns the fan duty to apply along with the
+/// updated integral and error to carry into the next tick. Kept free of any
+/// GPU/driver access so it can be unit tested without hardware.
+fn pid_step(config: &PidConfig, integral: f64, prev_error: f64, temperature: f64, dt: f64) -> (u32, f64, f64) {
+    let error = config.target_temp - temperature;
+    let integral = (integral + error * dt).clamp(-100.0, 100.0);
+    let derivative = (error - prev_error) / dt;
+
+    // error is target - current, so invert the PID terms around base_duty:
+    // a positive error (under target) lowers the duty below base, a
+    // negative error (over target) raises it above base
+    let duty = config.base_duty as f64 - (config.kp * error + config.ki * integral + config.kd * derivative);
+    let duty = (duty as i64).clamp(config.min_duty as i64, config.max_duty as i64) as u32;
+
+    (duty, integral, error)
+}
+
+/// Run a closed PID control loop per GPU, adjusting fan duty every
+/// `interval` to hold `config.target_temp`. The integral resets whenever
+/// `target_temp` changes between invocations, since callers construct a
+/// fresh `fan::run` per target.
+pub fn run(selected: &[(usize, &Gpu)], config: PidConfig, interval: Duration) -> () {
+    let mut states: Vec<PidState> = selected.iter().map(|_| PidState::new()).collect();
+
+    loop {
+        for ((global_idx, gpu), state) in selected.iter().zip(states.iter_mut()) {
+            let now = Instant::now();
+            let dt = match state.last_tick {
+                Some(last) => now.duration_since(last).as_secs_f64(),
+                None => interval.as_secs_f64(),
+            };
+            state.last_tick = Some(now);
+
+            let temperature = match read_temperature(gpu) {
+                Ok(temperature) => temperature,
+                Err(e) => {
+                    eprintln!("GPU #{}: failed to read temperature, skipping this tick ({})", global_idx, e);
+                    continue;
+                }
+            };
+
+            let (duty, integral, error) = pid_step(&config, state.integral, state.prev_error, temperature, dt);
+            state.integral = integral;
+            state.prev_error = error;
+
+            println!("GPU #{} at {:.1}C, setting fan duty to {}%", global_idx, temperature, duty);
+
+            set_fan_duty(gpu, duty);
+        }
+
+        sleep(interval);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> PidConfig {
+        PidConfig {
+            target_temp: 60.0,
+            kp: 2.0,
+            ki: 0.1,
+            kd: 0.5,
+            base_duty: 50,
+            min_duty: 30,
+            max_duty: 100,
+        }
+    }
+
+    #[test]
+    fn holds_base_duty_at_target() {
+        let (duty, integral, error) = pid_step(&config(), 0.0, 0.0, 60.0, 1.0);
+
+        assert_eq!(duty, 50);
+        assert_eq!(integral, 0.0);
+        assert_eq!(error, 0.0);
+    }
+
+    #[test]
+    fn raises_duty_above_base_when_over_target() {
+        let (duty, ..) = pid_step(&config(), 0.0, 0.0, 70.0, 1.0);
+
+        assert!(duty > 50, "expected duty above base_duty when over target, got {}", duty);
+    }
+
+    #[test]
+    fn lowers_duty_below_base_when_under_target() {
+        let (duty, ..) = pid_step(&config(), 0.0, 0.0, 50.0, 1.0);
+
+        assert!(duty < 50, "expected duty below base_duty when under target, got {}", duty);
+    }
+
+    #[test]
+    fn clamps_to_min_and_max_duty() {
+        let (duty, ..) = pid_step(&config(), 0.0, 0.0, 200.0, 1.0);
+        assert_eq!(duty, 100);
+
+        let (duty, ..) = pid_step(&config(), 0.0, 0.0, -200.0, 1.0);
+        assert_eq!(duty, 30);
+    }
+}
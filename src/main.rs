@@ -1,5 +1,17 @@
 extern crate num_traits;
 extern crate nvapi_hi as nvapi;
+extern crate thiserror;
+
+mod config;
+mod error;
+mod fan;
+mod monitor;
+
+use std::ops::RangeInclusive;
+use std::process::ExitCode;
+use std::time::Duration;
+
+use error::AppError;
 
 use cli_table::{format::Justify, print_stdout, Cell, Style, Table};
 use num_traits::Num;
@@ -25,14 +37,21 @@ use nvapi::{
     Status
 };
 
-fn parse_arg<T: Num> (matches: &ArgMatches, param: &str, expected_len: usize) -> Option<Vec<T>> {
+fn parse_arg<T: Num> (matches: &ArgMatches, param: &str, expected_len: usize) -> Result<Option<Vec<T>>, AppError> {
     match matches.values_of(param) {
         Some(values) => {
-            let values = values.map(|v| T::from_str_radix(v, 10).ok().unwrap()).collect::<Vec<T>>();
-            if values.len() != expected_len {
+            let mut parsed = Vec::new();
+
+            for v in values {
+                let value = T::from_str_radix(v, 10)
+                    .map_err(|_| AppError::Parse(format!("'{}' is not a valid value for '--{}'", v, param)))?;
+
+                parsed.push(value);
+            }
 
+            if parsed.len() != expected_len {
                 let error = Error {
-                    message: format!("Wrong number of '{}' values, got {}, expected {}", param, values.len(), expected_len),
+                    message: format!("Wrong number of '{}' values, got {}, expected {}", param, parsed.len(), expected_len),
                     kind: ErrorKind::WrongNumberOfValues,
                     info: None
                 };
@@ -40,61 +59,266 @@ fn parse_arg<T: Num> (matches: &ArgMatches, param: &str, expected_len: usize) ->
                 error.exit();
             }
 
-            Some(values)
+            Ok(Some(parsed))
         },
-        None => None
+        None => Ok(None)
     }
 }
 
-fn select_gus <'a>(gpus: &'a Vec<Gpu>, matches: &ArgMatches) -> Vec<(usize, &'a Gpu)> {
-    let selected_ids: Vec<(usize, &'a Gpu)> = matches.values_of("ids")
-        .unwrap()
-        .map(|val| {
-            let idx = usize::from_str_radix(val, 10).unwrap();
-            (idx, &gpus[idx])
-        })
-        .collect::<Vec<(usize, &'a Gpu)>>();
+/// Reject a requested delta that falls outside the hardware-reported VFP
+/// range for `field` on GPU `global_idx`, naming the GPU and the valid bounds.
+fn check_vfp_range(global_idx: usize, field: &str, value: i32, range: RangeInclusive<i32>) -> Result<(), AppError> {
+    if !range.contains(&value) {
+        return Err(AppError::Validation(format!(
+            "GPU #{}: requested {} of {} is outside the hardware-reported range [{}, {}]",
+            global_idx, field, value, range.start(), range.end()
+        )));
+    }
 
-    selected_ids
+    Ok(())
 }
 
-fn main() -> () {
+/// Check every requested clock/voltage offset for one GPU against its
+/// hardware-reported VFP ranges. Called as a full up-front pass over every
+/// selected GPU before any of them are touched, so a bad value on GPU #2
+/// can't be discovered after GPU #0 and #1 have already been reconfigured.
+fn validate_overclock(
+    global_idx: usize,
+    gpu: &Gpu,
+    memclock: Option<i32>,
+    gpuclock: Option<i32>,
+    voltage: Option<i32>,
+) -> Result<(), AppError> {
+    if memclock.is_none() && gpuclock.is_none() && voltage.is_none() {
+        return Ok(());
+    }
+
+    let ranges = gpu.inner().vfp_ranges()?;
+
+    if let Some(memclock) = memclock {
+        check_vfp_range(global_idx, "memclock", memclock, ranges.memory.clone())?;
+    }
+
+    if let Some(gpuclock) = gpuclock {
+        check_vfp_range(global_idx, "gpuclock", gpuclock, ranges.graphics.clone())?;
+    }
+
+    if let Some(voltage) = voltage {
+        check_vfp_range(global_idx, "voltage", voltage, ranges.voltage.clone())?;
+    }
+
+    Ok(())
+}
+
+fn apply_overclock(
+    global_idx: usize,
+    gpu: &Gpu,
+    memclock: Option<i32>,
+    gpuclock: Option<i32>,
+    voltage: Option<i32>,
+    powerlimit: Option<u32>,
+    templimit: Option<i32>,
+) -> Result<(), AppError> {
+    // memory clock
+    if let Some(memclock) = memclock {
+        let delta = KilohertzDelta(memclock);
+
+        println!("Setting GPU #{} memory clock to {:?}", global_idx, delta);
+
+        gpu.inner().set_pstates([(PState::P0, ClockDomain::Memory, delta)].iter().cloned())?;
+    }
+
+    // graphics clock
+    if let Some(gpuclock) = gpuclock {
+        let delta = KilohertzDelta(gpuclock);
+
+        println!("Setting GPU #{} graphics clock to {:?}", global_idx, delta);
+
+        gpu.inner().set_pstates([(PState::P0, ClockDomain::Graphics, delta)].iter().cloned())?;
+    }
+
+    // voltage over-volt offset
+    if let Some(voltage) = voltage {
+        let offset = Microvolts(voltage);
+
+        println!("Setting GPU #{} voltage offset to {:?}", global_idx, offset);
+
+        gpu.inner().set_voltage_boost(offset)?;
+    }
+
+    // power limit
+    if let Some(powerlimit) = powerlimit {
+        let limit = PowerLimit {
+            id: 0,
+            percentage: Percentage(powerlimit),
+        };
+
+        println!("Setting GPU #{} power limit to {:?}", global_idx, limit);
+
+        gpu.inner().set_power_limit(limit)?;
+    }
+
+    // thermal limit
+    if let Some(templimit) = templimit {
+        let limit = SensorLimit {
+            id: 0,
+            limit: Celsius(templimit),
+        };
+
+        println!("Setting GPU #{} thermal limit to {:?}", global_idx, limit);
+
+        gpu.inner().set_sensor_limit(limit)?;
+    }
+
+    Ok(())
+}
+
+/// Parse a single CLI value as `T`, wrapping a failure in an `AppError`
+/// instead of unwrapping, so callers can propagate it through `run()`.
+fn parse_num<T: Num>(value: &str, field: &str) -> Result<T, AppError> {
+    T::from_str_radix(value, 10)
+        .map_err(|_| AppError::Parse(format!("'{}' is not a valid value for '--{}'", value, field)))
+}
+
+fn select_gus <'a>(gpus: &'a Vec<Gpu>, matches: &ArgMatches) -> Result<Vec<(usize, &'a Gpu)>, AppError> {
+    let ids = matches.values_of("ids")
+        .ok_or_else(|| AppError::Parse("Expected a list of GPU indexes".to_string()))?;
+
+    ids.map(|val| {
+        let idx = parse_num::<usize>(val, "ids")?;
+
+        gpus.get(idx)
+            .map(|gpu| (idx, gpu))
+            .ok_or_else(|| AppError::Parse(format!("GPU index {} is out of range (have {} GPUs)", idx, gpus.len())))
+    }).collect::<Result<Vec<(usize, &'a Gpu)>, AppError>>()
+}
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+
+            ExitCode::from(e.exit_code() as u8)
+        }
+    }
+}
+
+fn run() -> Result<(), AppError> {
     let matches = App::new("micro-oc")
         .arg(Arg::with_name("ids")
             .multiple(true)
             .takes_value(true)
             .help("The list of GPU indexes, space separated"))
+        .arg(Arg::with_name("config")
+            .long("config")
+            .takes_value(true)
+            .help("Path to a TOML file of saved overclock profiles"))
         .subcommand(SubCommand::with_name("set")
             .arg(Arg::with_name("memclock")
                 .long("memclock")
                 .multiple(true)
                 .takes_value(true)
-                .help("Memory clock offset (kHz)")))
+                .help("Memory clock offset (kHz)"))
+            .arg(Arg::with_name("gpuclock")
+                .long("gpuclock")
+                .multiple(true)
+                .takes_value(true)
+                .help("Graphics clock offset (kHz)"))
+            .arg(Arg::with_name("voltage")
+                .long("voltage")
+                .multiple(true)
+                .takes_value(true)
+                .help("Voltage over-volt offset (uV)"))
+            .arg(Arg::with_name("powerlimit")
+                .long("powerlimit")
+                .multiple(true)
+                .takes_value(true)
+                .help("Power limit (%)"))
+            .arg(Arg::with_name("templimit")
+                .long("templimit")
+                .multiple(true)
+                .takes_value(true)
+                .help("Temperature limit (C)")))
         .subcommand(SubCommand::with_name("list"))
         .subcommand(SubCommand::with_name("reset"))
+        .subcommand(SubCommand::with_name("apply")
+            .arg(Arg::with_name("profile")
+                .required(true)
+                .takes_value(true)
+                .help("Name of the profile to apply from the config file")))
+        .subcommand(SubCommand::with_name("monitor")
+            .arg(Arg::with_name("interval")
+                .long("interval")
+                .takes_value(true)
+                .default_value("1000")
+                .help("Refresh interval in milliseconds"))
+            .arg(Arg::with_name("basic")
+                .long("basic")
+                .takes_value(false)
+                .help("Condense to one line per GPU for narrow terminals")))
+        .subcommand(SubCommand::with_name("fan")
+            .arg(Arg::with_name("target-temp")
+                .long("target-temp")
+                .takes_value(true)
+                .help("Target temperature (C) to hold via a PID control loop"))
+            .arg(Arg::with_name("kp")
+                .long("kp")
+                .takes_value(true)
+                .default_value("2.0")
+                .help("Proportional gain"))
+            .arg(Arg::with_name("ki")
+                .long("ki")
+                .takes_value(true)
+                .default_value("0.1")
+                .help("Integral gain"))
+            .arg(Arg::with_name("kd")
+                .long("kd")
+                .takes_value(true)
+                .default_value("0.5")
+                .help("Derivative gain"))
+            .arg(Arg::with_name("base")
+                .long("base")
+                .takes_value(true)
+                .default_value("50")
+                .help("Base fan duty (%) the PID terms are applied around"))
+            .arg(Arg::with_name("interval")
+                .long("interval")
+                .takes_value(true)
+                .default_value("1000")
+                .help("Control loop tick interval in milliseconds"))
+            .arg(Arg::with_name("min")
+                .long("min")
+                .takes_value(true)
+                .default_value("30")
+                .help("Minimum fan duty (%)"))
+            .arg(Arg::with_name("max")
+                .long("max")
+                .takes_value(true)
+                .default_value("100")
+                .help("Maximum fan duty (%)")))
         .get_matches();
 
-    nvapi::initialize().unwrap();
+    nvapi::initialize()?;
 
-    let gpus = Gpu::enumerate().unwrap();
+    let gpus = Gpu::enumerate()?;
     let info: Vec<GpuInfo> = gpus.iter()
-        .map(|gpu: &Gpu| Ok::<GpuInfo, Status>(gpu.info().unwrap()))
-        .collect::<Result<Vec<GpuInfo>, Status>>()
-        .unwrap();
+        .map(|gpu: &Gpu| gpu.info())
+        .collect::<Result<Vec<GpuInfo>, Status>>()?;
 
     match matches.subcommand() {
         ("list", Some(..)) => {
             let table = info.iter()
                 .zip(gpus.iter())
                 .enumerate()
-                .map(|(i, (info, gpu))| {
-                    vec![
-                        format!("GPU #{}", i).cell().justify(Justify::Left),
-                        info.name.clone().cell().justify(Justify::Left),
-                        info.vendor.clone().cell().justify(Justify::Left),
-                        gpu.inner().gpu_id().unwrap().cell().justify(Justify::Right),
-                    ]
-                })
+                .map(|(i, (info, gpu))| Ok::<_, Status>(vec![
+                    format!("GPU #{}", i).cell().justify(Justify::Left),
+                    info.name.clone().cell().justify(Justify::Left),
+                    info.vendor.clone().cell().justify(Justify::Left),
+                    gpu.inner().gpu_id()?.cell().justify(Justify::Right),
+                ]))
+                .collect::<Result<Vec<_>, Status>>()?
+                .into_iter()
                 .table()
                 .title(vec![
                     "GPU Index".cell().bold(true),
@@ -106,23 +330,103 @@ fn main() -> () {
             assert!(print_stdout(table).is_ok());
         },
         ("set", Some(inner_matches)) => {
-            let selected_gpus = select_gus(&gpus, &matches);
+            let selected_gpus = select_gus(&gpus, &matches)?;
 
-            let memclock = parse_arg::<i32>(inner_matches, "memclock", selected_gpus.len());
+            let memclock = parse_arg::<i32>(inner_matches, "memclock", selected_gpus.len())?;
+            let gpuclock = parse_arg::<i32>(inner_matches, "gpuclock", selected_gpus.len())?;
+            let voltage = parse_arg::<i32>(inner_matches, "voltage", selected_gpus.len())?;
+            let powerlimit = parse_arg::<u32>(inner_matches, "powerlimit", selected_gpus.len())?;
+            let templimit = parse_arg::<i32>(inner_matches, "templimit", selected_gpus.len())?;
 
             for (i, (global_idx, gpu)) in selected_gpus.iter().enumerate() {
-                // memory clock
-                // TODO: validate using gpu.inner().vfp_ranges()
-                match &memclock {
-                    Some(memclock) => {
-                        let delta = KilohertzDelta(memclock[i]);
+                validate_overclock(
+                    *global_idx,
+                    gpu,
+                    memclock.as_ref().map(|v| v[i]),
+                    gpuclock.as_ref().map(|v| v[i]),
+                    voltage.as_ref().map(|v| v[i]),
+                )?;
+            }
 
-                        println!("Setting GPU #{} memory clock to {:?}", global_idx, delta);
+            for (i, (global_idx, gpu)) in selected_gpus.iter().enumerate() {
+                apply_overclock(
+                    *global_idx,
+                    gpu,
+                    memclock.as_ref().map(|v| v[i]),
+                    gpuclock.as_ref().map(|v| v[i]),
+                    voltage.as_ref().map(|v| v[i]),
+                    powerlimit.as_ref().map(|v| v[i]),
+                    templimit.as_ref().map(|v| v[i]),
+                )?;
+            }
+        },
+        ("apply", Some(inner_matches)) => {
+            let config_path = matches.value_of("config").unwrap_or("micro-oc.toml");
+            let config = config::Config::load(config_path)?;
 
-                        gpu.inner().set_pstates([(PState::P0, ClockDomain::Memory, delta)].iter().cloned()).unwrap();
-                    },
-                    None => ()
-                };
+            let profile_name = inner_matches.value_of("profile").unwrap();
+            let profile = config.profile(profile_name)?;
+
+            let device_ids: Vec<u32> = gpus.iter()
+                .map(|gpu| gpu.inner().gpu_id())
+                .collect::<Result<Vec<u32>, Status>>()?;
+
+            config::validate_profile(profile, &device_ids)?;
+
+            for entry in &profile.gpus {
+                let global_idx = device_ids.iter().position(|id| *id == entry.device_id).unwrap();
+                let gpu = &gpus[global_idx];
+
+                validate_overclock(global_idx, gpu, entry.memclock, entry.gpuclock, entry.voltage)?;
+            }
+
+            for entry in &profile.gpus {
+                let global_idx = device_ids.iter().position(|id| *id == entry.device_id).unwrap();
+                let gpu = &gpus[global_idx];
+
+                apply_overclock(
+                    global_idx,
+                    gpu,
+                    entry.memclock,
+                    entry.gpuclock,
+                    entry.voltage,
+                    entry.powerlimit,
+                    entry.templimit,
+                )?;
+            }
+        },
+        ("monitor", Some(inner_matches)) => {
+            let selected_gpus = select_gus(&gpus, &matches)?;
+            let selected_info: Vec<&GpuInfo> = selected_gpus.iter()
+                .map(|(global_idx, _)| &info[*global_idx])
+                .collect();
+
+            let interval = parse_num::<u64>(inner_matches.value_of("interval").unwrap(), "interval")?;
+            let basic = inner_matches.is_present("basic");
+
+            monitor::run(&selected_gpus, &selected_info, Duration::from_millis(interval), basic);
+        },
+        ("fan", Some(inner_matches)) => {
+            let selected_gpus = select_gus(&gpus, &matches)?;
+
+            match inner_matches.value_of("target-temp") {
+                Some(target_temp) => {
+                    let config = fan::PidConfig {
+                        target_temp: parse_num::<f64>(target_temp, "target-temp")?,
+                        kp: parse_num::<f64>(inner_matches.value_of("kp").unwrap(), "kp")?,
+                        ki: parse_num::<f64>(inner_matches.value_of("ki").unwrap(), "ki")?,
+                        kd: parse_num::<f64>(inner_matches.value_of("kd").unwrap(), "kd")?,
+                        base_duty: parse_num::<u32>(inner_matches.value_of("base").unwrap(), "base")?,
+                        min_duty: parse_num::<u32>(inner_matches.value_of("min").unwrap(), "min")?,
+                        max_duty: parse_num::<u32>(inner_matches.value_of("max").unwrap(), "max")?,
+                    };
+                    let interval = parse_num::<u64>(inner_matches.value_of("interval").unwrap(), "interval")?;
+
+                    fan::run(&selected_gpus, config, Duration::from_millis(interval));
+                },
+                None => {
+                    return Err(AppError::Validation("The 'fan' subcommand currently requires --target-temp".to_string()));
+                }
             }
         },
         ("reset", Some(..)) => {
@@ -135,10 +439,12 @@ fn main() -> () {
                     (PState::P0, ClockDomain::Memory, KilohertzDelta(0)),
                 ].iter().cloned();
 
-                gpu.inner().set_pstates(deltas).unwrap();
+                gpu.inner().set_pstates(deltas)?;
             }
         },
         ("", ..) => (),
         _ => unreachable!("unknown command"),
     }
+
+    Ok(())
 }
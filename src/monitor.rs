@@ -0,0 +1,151 @@
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use cli_table::{format::Justify, print_stdout, Cell, Style, Table};
+
+use nvapi::{ClockDomain, Gpu, GpuInfo};
+
+/// A single GPU's readout for one refresh of the dashboard.
+struct GpuSample {
+    temperature: i32,
+    graphics_clock: i32,
+    memory_clock: i32,
+    utilization: u32,
+    power_draw: u32,
+    fan_speed: u32,
+}
+
+/// Read one GPU's sensors for the current tick. A transient driver read
+/// failure, or a GPU reporting an empty/partial reading (e.g. no
+/// controllable cooler), should not take down an otherwise long-running
+/// `monitor` session, so callers log and skip this GPU for the tick
+/// instead of propagating the error.
+fn sample_gpu(gpu: &Gpu) -> Result<GpuSample, String> {
+    let thermal = gpu.inner().thermal_settings(None).map_err(|e| e.to_string())?;
+    let clocks = gpu.inner().clocks().map_err(|e| e.to_string())?;
+    let usage = gpu.inner().usages().map_err(|e| e.to_string())?;
+    let power = gpu.inner().power_usage().map_err(|e| e.to_string())?;
+    let cooler = gpu.inner().cooler_levels().map_err(|e| e.to_string())?;
+
+    Ok(GpuSample {
+        temperature: thermal.get(0).ok_or("no thermal sensor reported")?.current_temperature,
+        graphics_clock: clocks.get(&ClockDomain::Graphics).ok_or("no graphics clock reported")?.0,
+        memory_clock: clocks.get(&ClockDomain::Memory).ok_or("no memory clock reported")?.0,
+        utilization: usage.gpu.0,
+        power_draw: power.get(0).ok_or("no power sensor reported")?.power.0,
+        fan_speed: cooler.get(0).ok_or("no controllable cooler reported")?.current_level.0,
+    })
+}
+
+/// Gates sensor refreshes behind an elapsed-time check, the same way
+/// sysinfo's `CpusWrapper` avoids hammering the driver on every tick.
+struct Throttle {
+    interval: Duration,
+    last_update: Option<Instant>,
+}
+
+impl Throttle {
+    fn new(interval: Duration) -> Self {
+        Throttle { interval, last_update: None }
+    }
+
+    fn ready(&self) -> bool {
+        match self.last_update {
+            Some(last) => last.elapsed() >= self.interval,
+            None => true,
+        }
+    }
+
+    fn mark(&mut self) {
+        self.last_update = Some(Instant::now());
+    }
+}
+
+fn render_basic(selected: &[(usize, &Gpu)], info: &[&GpuInfo]) {
+    for ((global_idx, gpu), info) in selected.iter().zip(info.iter()) {
+        let sample = match sample_gpu(gpu) {
+            Ok(sample) => sample,
+            Err(e) => {
+                eprintln!("GPU #{}: failed to read sensors, skipping this tick ({})", global_idx, e);
+                continue;
+            }
+        };
+
+        println!(
+            "GPU #{} {:<20} {}C  core {}MHz  mem {}MHz  util {}%  {}W  fan {}%",
+            global_idx,
+            info.name,
+            sample.temperature,
+            sample.graphics_clock / 1000,
+            sample.memory_clock / 1000,
+            sample.utilization,
+            sample.power_draw,
+            sample.fan_speed,
+        );
+    }
+}
+
+fn render_table(selected: &[(usize, &Gpu)], info: &[&GpuInfo]) {
+    let rows = selected.iter()
+        .zip(info.iter())
+        .filter_map(|((global_idx, gpu), info)| {
+            let sample = match sample_gpu(gpu) {
+                Ok(sample) => sample,
+                Err(e) => {
+                    eprintln!("GPU #{}: failed to read sensors, skipping this tick ({})", global_idx, e);
+                    return None;
+                }
+            };
+
+            Some(vec![
+                format!("GPU #{}", global_idx).cell().justify(Justify::Left),
+                info.name.clone().cell().justify(Justify::Left),
+                format!("{}C", sample.temperature).cell().justify(Justify::Right),
+                format!("{} MHz", sample.graphics_clock / 1000).cell().justify(Justify::Right),
+                format!("{} MHz", sample.memory_clock / 1000).cell().justify(Justify::Right),
+                format!("{}%", sample.utilization).cell().justify(Justify::Right),
+                format!("{} W", sample.power_draw).cell().justify(Justify::Right),
+                format!("{}%", sample.fan_speed).cell().justify(Justify::Right),
+            ])
+        });
+
+    let table = rows
+        .table()
+        .title(vec![
+            "GPU Index".cell().bold(true),
+            "Name".cell().bold(true),
+            "Temp".cell().bold(true),
+            "Core Clock".cell().bold(true),
+            "Mem Clock".cell().bold(true),
+            "Utilization".cell().bold(true),
+            "Power".cell().bold(true),
+            "Fan".cell().bold(true),
+        ]);
+
+    assert!(print_stdout(table).is_ok());
+}
+
+/// Run a continuously-updating sensor dashboard for the selected GPUs,
+/// refreshing at most once per `interval` to avoid hammering the driver.
+pub fn run(selected: &[(usize, &Gpu)], info: &[&GpuInfo], interval: Duration, basic: bool) -> () {
+    let mut throttle = Throttle::new(interval);
+
+    loop {
+        if throttle.ready() {
+            if !basic {
+                // clear the screen so the table redraws in place
+                print!("\x1B[2J\x1B[1;1H");
+            }
+
+            if basic {
+                render_basic(selected, info);
+            } else {
+                render_table(selected, info);
+            }
+
+            throttle.mark();
+        }
+
+        sleep(Duration::from_millis(100));
+    }
+}